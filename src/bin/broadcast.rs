@@ -1,6 +1,5 @@
 use anyhow::Context;
 use async_trait::async_trait;
-use rand::prelude::*;
 use rustengan::*;
 use serde::{Deserialize, Serialize};
 use std::{
@@ -8,13 +7,7 @@ use std::{
     sync::Arc,
     time::Duration,
 };
-use tokio::{
-    io::{AsyncWriteExt, Stdout},
-    select, spawn,
-    sync::Mutex,
-    task::{self, JoinHandle},
-    time::sleep,
-};
+use tokio::{io::Stdout, sync::Mutex};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 // Serde decorator to call Payload as type
@@ -43,6 +36,10 @@ enum Payload {
     Gossip {
         seen: HashSet<usize>,
     },
+
+    // Never sent over the wire: injected by our own `tokio::time::interval` (see `on_init`)
+    // to trigger a gossip round on a fixed schedule, independent of stdin traffic.
+    GossipTick {},
 }
 
 struct BroadcastNode {
@@ -84,9 +81,19 @@ impl Node<(), Payload> for BroadcastNode {
     // fn step to act at any given message depending on its payload
     async fn step<'a>(
         &mut self,
-        input: Message<Payload>,
+        input: Event<Payload>,
         output: &'a mut Arc<Mutex<Stdout>>,
     ) -> anyhow::Result<()> {
+        let input = match input {
+            Event::Message(input) => input,
+            // Our own interval timer asking for a gossip round; it has no sender to reply to.
+            Event::Injected(Payload::GossipTick {}) => {
+                self.gossip(output).await;
+                return Ok(());
+            }
+            Event::Injected(_) | Event::Eof => return Ok(()),
+        };
+
         // Match for every possible event
 
         let mut reply = input.clone().into_reply(Some(&mut self.id));
@@ -129,11 +136,34 @@ impl Node<(), Payload> for BroadcastNode {
             }
 
             // A way to group up different matches with the same handler
-            Payload::BroadcastOk { .. } | Payload::ReadOk { .. } | Payload::TopologyOk {} => {}
+            Payload::BroadcastOk { .. } | Payload::ReadOk { .. } | Payload::TopologyOk {}
+            | Payload::GossipTick {} => {}
         }
         Ok(())
     }
 
+    // Kicks off a `GossipTick` on a fixed interval, independent of stdin volume, so the
+    // nemesis partition can't starve gossip just by starving other traffic.
+    async fn on_init(&mut self, handle: InitHandle<Payload>) {
+        let injector = handle.injector;
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_millis(300));
+            loop {
+                interval.tick().await;
+                if injector
+                    .send(Event::Injected(Payload::GossipTick {}))
+                    .await
+                    .is_err()
+                {
+                    // Node's gone (main_loop returned); stop ticking.
+                    break;
+                }
+            }
+        });
+    }
+}
+
+impl BroadcastNode {
     // CHECK IF ITS BETTER TO HAVE AN ARC REFERENCE TO STDOUT HERE AND IN THE SEND FUNCTION --> Actually, it has more sense
     async fn gossip<'a>(&mut self, output: &'a mut Arc<Mutex<Stdout>>) {
         for n in &self.neighborhood {
@@ -143,10 +173,10 @@ impl Node<(), Payload> for BroadcastNode {
             eprintln!("sending gossip {:?} to {}", self.messages, n);
             eprintln!("known messages {:?} to {}", known_to_n, n);
 
-            let _ = Message {
-                src: self.node.clone(),
-                dst: n.clone(),
-                body: Body {
+            let _ = Message::addressed_to(
+                self.node.clone(),
+                &ServiceTarget::Node(n.clone()),
+                Body {
                     id: None,
                     in_reply_to: None,
                     payload: Payload::Gossip {
@@ -157,8 +187,9 @@ impl Node<(), Payload> for BroadcastNode {
                             .filter(|m| !known_to_n.contains(m))
                             .collect(),
                     },
+                    meta: NoMeta::default(),
                 },
-            }
+            )
             .send(&mut *output)
             .await;
         }
@@ -1,13 +1,8 @@
 use std::sync::Arc;
 
-use anyhow::Context;
 use rustengan::*;
 use serde::{Deserialize, Serialize};
-use tokio::{
-    io::{AsyncWriteExt, Stdout},
-    sync::Mutex,
-    task::{self, JoinHandle},
-};
+use tokio::{io::Stdout, sync::Mutex};
 use async_trait::async_trait;
 
 
@@ -28,7 +23,7 @@ struct EchoNode {
 // Implementation of the trait Node for EchoNode
 #[async_trait]
 impl Node<(), Payload> for EchoNode {
-    async fn from_init<'a>(_state: (), init: Init) -> anyhow::Result<Self>
+    async fn from_init<'a>(_state: (), _init: Init) -> anyhow::Result<Self>
     where
         Self: Sized,
     {
@@ -36,7 +31,10 @@ impl Node<(), Payload> for EchoNode {
     }
 
     // fn step to act at any given message depending on its payload
-    async fn step<'a>(&mut self, input: Message<Payload>, output:  &'a mut Arc<Mutex<Stdout>>) -> anyhow::Result<()> {
+    async fn step<'a>(&mut self, input: Event<Payload>, output:  &'a mut Arc<Mutex<Stdout>>) -> anyhow::Result<()> {
+        let Event::Message(input) = input else {
+            return Ok(());
+        };
         let mut output_clone = Arc::clone(output);
 
         match input.body.payload {
@@ -48,6 +46,7 @@ impl Node<(), Payload> for EchoNode {
                         id: Some(self.id),
                         in_reply_to: input.body.id,
                         payload: Payload::EchoOk { echo },
+                        meta: NoMeta::default(),
                     },
                 };
                 let _ = reply.send(&mut output_clone).await;
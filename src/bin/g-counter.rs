@@ -0,0 +1,114 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use rustengan::*;
+use serde::{Deserialize, Serialize};
+use tokio::{io::Stdout, sync::Mutex};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+// Serde decorator to call Payload as type
+#[serde(tag = "type")]
+// Serde decorator to convert every Enum payload into snake_cases
+#[serde(rename_all = "snake_case")]
+enum Payload {
+    Add { delta: i64 },
+    AddOk {},
+
+    Read {},
+    ReadOk { value: i64 },
+}
+
+// Key the counter lives under in seq-kv. One key shared by every node, CAS'd to stay consistent.
+const COUNTER_KEY: &str = "g-counter";
+
+struct GCounterNode {
+    id: usize,
+    // Filled in by `on_init`; every `step` call happens after that, so it's always `Some` by
+    // the time we need it.
+    rpc: Option<RpcHandle>,
+}
+
+// Implementation of the trait Node for GCounterNode
+#[async_trait]
+impl Node<(), Payload> for GCounterNode {
+    async fn from_init<'a>(_state: (), _init: Init) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(GCounterNode { id: 1, rpc: None })
+    }
+
+    async fn on_init(&mut self, handle: InitHandle<Payload>) {
+        self.rpc = Some(handle.rpc);
+    }
+
+    // fn step to act at any given message depending on its payload
+    async fn step<'a>(&mut self, input: Event<Payload>, output: &'a mut Arc<Mutex<Stdout>>) -> anyhow::Result<()> {
+        let Event::Message(input) = input else {
+            return Ok(());
+        };
+
+        let rpc = self
+            .rpc
+            .clone()
+            .expect("on_init runs before any message is dispatched");
+        let kv = Kv::new(&rpc, ServiceTarget::SeqKv);
+
+        let mut reply = input.into_reply(Some(&mut self.id));
+        match reply.body.payload {
+            Payload::Add { delta } => {
+                // Read-modify-write against seq-kv, retrying whenever another node's `add` won
+                // the race in between our read and our cas.
+                loop {
+                    let current: i64 = kv
+                        .read(COUNTER_KEY)
+                        .await
+                        .context("read current counter value")?
+                        .unwrap_or(0);
+
+                    match kv.cas(COUNTER_KEY, current, current + delta, true).await {
+                        Ok(()) => break,
+                        Err(e) if e.is_precondition_failed() => continue,
+                        Err(e) => return Err(e.into()),
+                    }
+                }
+
+                reply.body.payload = Payload::AddOk {};
+                reply.send(&mut *output).await.context("reply to add")?;
+            }
+
+            Payload::Read {} => {
+                // seq-kv is sequentially, not linearizably, consistent: a `read` right after
+                // another node's `add` can still observe a stale value. Force a write through
+                // the key first so this node's view is caught up before reading it back.
+                let current: i64 = kv
+                    .read(COUNTER_KEY)
+                    .await
+                    .context("read counter value")?
+                    .unwrap_or(0);
+                let _ = kv.cas(COUNTER_KEY, current, current, true).await;
+                let value: i64 = kv
+                    .read(COUNTER_KEY)
+                    .await
+                    .context("re-read counter value")?
+                    .unwrap_or(0);
+
+                reply.body.payload = Payload::ReadOk { value };
+                reply.send(&mut *output).await.context("reply to read")?;
+            }
+
+            Payload::AddOk {} | Payload::ReadOk { .. } => {}
+        }
+        Ok(())
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    //We call the main_loop function with a initial state (as we had the trait implemented for GCounterNode)
+    let _ = main_loop::<_, GCounterNode, _>(());
+    Ok(())
+}
+
+// command to run maelstrom g-counter test, has to be on maelstrom file where maelstrom exe is (have to indicate the rust compilation target too)
+// ./maelstrom test -w g-counter --bin ../../rustengan/target/debug/g-counter --node-count 3 --rate 100 --time-limit 20 --nemesis partition
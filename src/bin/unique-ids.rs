@@ -1,8 +1,10 @@
+use std::sync::Arc;
+
 use anyhow::Context;
+use async_trait::async_trait;
 use rustengan::*;
 use serde::{Deserialize, Serialize};
-//use ulid::Ulid;
-use std::io::{StdoutLock, Write};
+use tokio::{io::Stdout, sync::Mutex};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 // Serde decorator to call Payload as type
@@ -22,13 +24,10 @@ struct UniqueNode {
     id: usize,
 }
 
-// Implementation of the trait Node for EchoNode
+// Implementation of the trait Node for UniqueNode
+#[async_trait]
 impl Node<(), Payload> for UniqueNode {
-    fn from_init(
-        _state: (),
-        init: Init,
-        _sx: std::sync::mpsc::Sender<Event<Payload>>,
-    ) -> anyhow::Result<Self>
+    async fn from_init<'a>(_state: (), init: Init) -> anyhow::Result<Self>
     where
         Self: Sized,
     {
@@ -39,23 +38,19 @@ impl Node<(), Payload> for UniqueNode {
     }
 
     // fn step to act at any given message depending on its payload
-    fn step<'a>(&mut self, input: Event<Payload>, output: &mut StdoutLock) -> anyhow::Result<()> {
+    async fn step<'a>(&mut self, input: Event<Payload>, output: &'a mut Arc<Mutex<Stdout>>) -> anyhow::Result<()> {
         let Event::Message(input) = input else {
-            panic!("got injected event when there's no event injection");
+            return Ok(());
         };
 
         let mut reply = input.into_reply(Some(&mut self.id));
         match reply.body.payload {
             Payload::Generate {} => {
                 //crate to generate unique ids
-                //let guid = Ulid::new().to_string();
                 let guid = format!("{}-{}", self.node, self.id);
                 reply.body.payload = Payload::GenerateOk { guid };
 
-                // Serialize the rust struct into a json object with context in case of fail
-                serde_json::to_writer(&mut *output, &reply)
-                    .context("serialize response to generate")?;
-                let _ = output.write_all(b"\n").context("Write trailing newline");
+                reply.send(&mut *output).await.context("reply to generate")?;
                 self.id += 1;
             }
             Payload::GenerateOk { .. } => {}
@@ -66,7 +61,7 @@ impl Node<(), Payload> for UniqueNode {
 
 fn main() -> anyhow::Result<()> {
     //We call the main_loop function with a initial state (as we had the trait implemented for EchoNode)
-    let _ = main_loop::<_, UniqueNode, _, _>(());
+    let _ = main_loop::<_, UniqueNode, _>(());
     Ok(())
 }
 
@@ -1,91 +1,42 @@
 //Lib.rs contains all common code for the challenges to execute
+mod protocol;
+pub use protocol::*;
+
 use anyhow::Context;
 use async_trait::async_trait;
-use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
 use std::{
-    any::TypeId,
-    future::Future,
-    io::{stdin, BufRead, StdoutLock, Write},
+    collections::HashMap,
+    io::BufRead,
     sync::Arc, time::Duration,
 };
 use tokio::{
-    io::{AsyncWriteExt, Stdout},
-    sync::Mutex,
-    task::{self, JoinHandle}, time::sleep,
+    io::Stdout,
+    select,
+    sync::{mpsc, oneshot, Mutex},
+    task, time::timeout,
 };
 
-// Struct Message
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Message<Payload> {
-    pub src: String,
-    #[serde(rename = "dest")]
-    pub dst: String,
-    pub body: Body<Payload>,
-}
-
-// Implementation of the message for a generic type of payload
-impl<Payload> Message<Payload> {
-    pub fn into_reply(self, id: Option<&mut usize>) -> Self {
-        Self {
-            src: self.dst,
-            dst: self.src,
-            body: Body {
-                id: id.map(|id| {
-                    let mid = *id;
-                    *id += 1;
-                    mid
-                }),
-                in_reply_to: self.body.id,
-                payload: self.body.payload,
-            },
-        }
-    }
-
-    // Send method to reply for different messages
-    pub async fn send<'a>(&self, output: &'a mut Arc<Mutex<Stdout>>) -> anyhow::Result<()>
-    where
-        Payload: Serialize,
-    {
-        let output_clone = Arc::clone(output);
-        let mut output_lock = output_clone.lock().await;
-
-        output_lock
-            .write(&serde_json::to_vec(self).expect("Cannot convert to bytes"))
-            .await?;
-        output_lock.write(b"\n").await?;
-        Ok(())
-    }
-}
-
-// Body struct
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Body<Payload> {
-    #[serde(rename = "msg_id")]
-    pub id: Option<usize>,
-    pub in_reply_to: Option<usize>,
-    #[serde(flatten)]
-    pub payload: Payload,
+// Something `step` can be handed: either a message that actually came in over stdin, a value
+// a node injected into its own loop (e.g. a periodic gossip tick), or notice that stdin closed.
+pub enum Event<Payload> {
+    Message(Message<Payload>),
+    Injected(Payload),
+    Eof,
 }
 
-// InitPayload struct
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(tag = "type")]
-#[serde(rename_all = "snake_case")]
-enum InitPayload {
-    Init(Init),
-    InitOk,
-}
-
-// Init struct
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Init {
-    pub node_id: String,
-    pub node_ids: Vec<String>,
+// Handle a node gets once, right after `from_init`, for registering background work that
+// doesn't originate from stdin: periodic timers that inject events (`injector`), or RPCs to
+// other nodes/services (`rpc`).
+pub struct InitHandle<Payload> {
+    pub injector: mpsc::Sender<Event<Payload>>,
+    pub rpc: RpcHandle,
 }
 
 //Definition of trait Node
 #[async_trait]
-pub trait Node<S, Payload> {
+pub trait Node<S, Payload: Send + 'static> {
     // Init for the specific node
     async fn from_init<'a>(state: S, init: Init) -> anyhow::Result<Self>
     where
@@ -95,12 +46,14 @@ pub trait Node<S, Payload> {
 
     async fn step<'a>(
         &mut self,
-        input: Message<Payload>,
+        input: Event<Payload>,
         output: &'a mut Arc<Mutex<Stdout>>,
     ) -> anyhow::Result<()>;
 
-    async fn gossip<'a>(&mut self, output: &'a mut Arc<Mutex<Stdout>>){
-    }
+    // Called once after `from_init`. Nodes that need background work (broadcast's gossip
+    // timer, anything that wants to fire RPCs) override this to stash the handle; everyone
+    // else gets this no-op.
+    async fn on_init(&mut self, _handle: InitHandle<Payload>) {}
 }
 
 /*
@@ -111,27 +64,30 @@ tasks to perform: interchanging protocols and gossiping.
 #[tokio::main]
 pub async fn main_loop<S, N, P>(init_state: S) -> anyhow::Result<()>
 where
-    P: DeserializeOwned + Send + 'static,
+    P: DeserializeOwned + Clone + Send + 'static,
     N: Node<S, P> + Send + 'static,
 {
     // Init phase
 
-    // Lock the stdin for the init messages
-    let stdin = std::io::stdin().lock();
-    let mut stdin = stdin.lines();
-
     // Create the async stdout
     let stdout = tokio::io::stdout();
     let stdout = Arc::new(Mutex::new(stdout));
 
-    // Get the first msg from stdin to check if there's messages
-    let init_msg: Message<InitPayload> = serde_json::from_str(
-        &stdin
-            .next()
-            .expect("no init message received")
-            .context("failed to read init message from stdin")?,
-    )
-    .context("init message could not be deserialized")?;
+    // Get the first msg from stdin to check if there's messages. Scoped so the `StdinLock`
+    // (not `Send`) is dropped before the background stdin reader below locks it again itself --
+    // the lock just guards std's shared buffered reader, so the second lock picks up right
+    // where this one left off.
+    let init_msg: Message<InitPayload> = {
+        let stdin = std::io::stdin().lock();
+        let mut stdin = stdin.lines();
+        serde_json::from_str(
+            &stdin
+                .next()
+                .expect("no init message received")
+                .context("failed to read init message from stdin")?,
+        )
+        .context("init message could not be deserialized")?
+    };
 
     // Check if the first msg it's a init_msg
     let InitPayload::Init(init) = init_msg.body.payload else {
@@ -139,6 +95,7 @@ where
     };
 
     // If so, initialize the node and send a initOk reply
+    let node_id = init.node_id.clone();
     let node: N = Node::from_init(init_state, init).await?;
     let reply = Message {
         src: init_msg.dst,
@@ -147,6 +104,7 @@ where
             id: Some(0),
             in_reply_to: init_msg.body.id,
             payload: InitPayload::InitOk,
+            meta: NoMeta::default(),
         },
     };
 
@@ -159,11 +117,32 @@ where
     // Two thread-safe reference-counting pointer for shared values as the node and the stdout has both async properties and need to be thread-safe if we want to spawn async task
     let node = Arc::new(Mutex::new(node));
 
+    // RPC correlation: outgoing msg_ids registered here get routed their reply instead of
+    // going through `step`, so `rpc` below can be a plain `async fn` despite stdin being
+    // dispatched on its own tasks.
+    let pending: PendingReplies = Arc::new(Mutex::new(HashMap::new()));
+    let rpc_handle = RpcHandle {
+        src: node_id.clone(),
+        next_msg_id: Arc::new(Mutex::new(1)),
+        pending: Arc::clone(&pending),
+        output: Arc::clone(&stdout),
+    };
+
 
 
-    // I THINK WE NEED A LOOP FOR THE GOSSIP SO THE NEMESIS PARTITTION (NO COMMUNICATION IN A CONCRETE TIME WINDOW) CAN BE MANAGED
-    //let futere  = node.clone().lock().await.gossip(&mut stdout.clone());
-    
+    // Injection channel: lets a node's own background tasks (a gossip interval, a retry timer)
+    // feed events into the loop below on their own schedule, instead of piggybacking on
+    // whatever stdin traffic happens to arrive. Handed out once via `on_init`.
+    let (inject_tx, mut inject_rx) = mpsc::channel::<Event<P>>(1024);
+    {
+        let mut node_lock = node.lock().await;
+        node_lock
+            .on_init(InitHandle {
+                injector: inject_tx,
+                rpc: rpc_handle,
+            })
+            .await;
+    }
 
     /* CLARIFICATION ARC-MUTEX
     In Rust, values are moved when they are passed to functions or closures, and by default they cannot be used again after being moved unless they implement the Copy trait.
@@ -174,55 +153,112 @@ where
     The Arc (Atomic Reference Counted) and Mutex (Mutual Exclusion) types from the Rust standard library can be used like this to share safely between iterations:
 
      */
-    // For every line we get from the sync stdin:
     /*NOTE ON STDIN
     Maelstrom will redirect every message nodes write onto stdout to stdin, this was discovered as we introduced gossip messages so the total load of message increased
-    significally    
+    significally
      */
-    let mut line_iter  = 0; 
-    for line in stdin {
-        eprint!("iteration of loop lines nº{}", line_iter);
-        eprintln!(" line emited: {}", line.as_ref().expect("no line"));
-
-        line_iter += 1;
-
-        // We clone the Arc (not the stdout and the node themself), which increments the reference count but doesn't duplicate the underlying object.
-        let node_clone = Arc::clone(&node);
-        let mut stdout_clone = Arc::clone(&stdout);
-
-        // Parsing the stdin lines
-        let line = line
-            .context("Maelstrom input from STDIN could not be read")
-            .expect("Error on STDIN read");
-        let input: Message<P> = serde_json::from_str(&line)
-            .context("Maelstrom input from STDIN could not be deserialized")
-            .expect("Expected message from STDIN");
-
-        /* NOTE ON TOKIO::SPAWN
-        Spawning a task enables the task to execute concurrently to other tasks. The spawned task may execute on the current thread, or it may be sent to a different thread to be executed.
-        The specifics depend on the current Runtime configuration.
-
-        It is guaranteed that spawn will not synchronously poll the task being spawned. This means that calling spawn while holding a lock does not pose a risk of deadlocking with the spawned task.
-
-        As for these facts, we need that everything inside the spawn block is thread-safe.
-         */
-        // For every line we spawn async tasks that will perform the protocol readed from stdin. We need to lock shared resources for every tasks in order to avoid concurrency
-        
-        tasks.push(tokio::spawn(async move {
-            // Lock on both the node and the stdout
-            let mut node_lock = node_clone.lock().await;
-            // A rough wait to manage the gossip load, we will gossip every two lines readed, so we ensure we reduce the total number of gossip message by half
-            if line_iter % 2 == 0 {let _ = node_lock.gossip(&mut stdout_clone).await;}
-             // Performing the step function for every task
-            node_lock.step(input, &mut stdout_clone).await
-            
-        }));
+    // `stdin.lines()` is a blocking iterator, so it can't be polled directly inside `select!`.
+    // Read it on a blocking task instead and forward each line over a channel the loop below
+    // can select on alongside `inject_rx`.
+    let (line_tx, mut line_rx) = mpsc::channel::<String>(1024);
+    let stdin_task = task::spawn_blocking(move || {
+        let stdin = std::io::stdin();
+        for line in stdin.lock().lines() {
+            let line = line.context("Maelstrom input from STDIN could not be read")?;
+            if line_tx.blocking_send(line).is_err() {
+                break;
+            }
+        }
+        Ok::<_, anyhow::Error>(())
+    });
+
+    let mut line_iter = 0;
+    // Once a node's `on_init` drops its `injector` (the default no-op never stashes one), the
+    // channel closes and `inject_rx.recv()` would resolve to `None` immediately forever; guard
+    // the arm so `select!` stops polling it instead of busy-looping.
+    let mut inject_open = true;
+    loop {
+        select! {
+            line = line_rx.recv() => {
+                let Some(line) = line else {
+                    // stdin closed: let the node know, then stop dispatching.
+                    let mut node_lock = node.lock().await;
+                    let mut stdout_clone = Arc::clone(&stdout);
+                    let _ = node_lock.step(Event::Eof, &mut stdout_clone).await;
+                    break;
+                };
+                eprintln!("iteration of loop lines nº{}: {}", line_iter, line);
+                line_iter += 1;
+
+                // Peek at `in_reply_to` before committing to a typed `Message<P>` parse: RPC
+                // replies (e.g. from a KV service) carry payload shapes P does not know about,
+                // so we can only decode them as raw JSON here. If an `rpc` caller is waiting on
+                // this msg_id, route the whole body to their oneshot and skip `step` entirely.
+                let peeked: Value = serde_json::from_str(&line)
+                    .context("Maelstrom input from STDIN could not be parsed as JSON")
+                    .expect("Expected JSON message from STDIN");
+                let in_reply_to = peeked["body"]["in_reply_to"].as_u64().map(|id| id as usize);
+
+                if let Some(id) = in_reply_to {
+                    let waiter = pending.lock().await.remove(&id);
+                    if let Some(tx) = waiter {
+                        let _ = tx.send(peeked["body"].clone());
+                        continue;
+                    }
+                }
+
+                let input: Message<P> = serde_json::from_value(peeked)
+                    .context("Maelstrom input from STDIN could not be deserialized")
+                    .expect("Expected message from STDIN");
+
+                // We clone the Arc (not the stdout and the node themself), which increments the reference count but doesn't duplicate the underlying object.
+                let node_clone = Arc::clone(&node);
+                let mut stdout_clone = Arc::clone(&stdout);
+
+                /* NOTE ON TOKIO::SPAWN
+                Spawning a task enables the task to execute concurrently to other tasks. The spawned task may execute on the current thread, or it may be sent to a different thread to be executed.
+                The specifics depend on the current Runtime configuration.
+
+                It is guaranteed that spawn will not synchronously poll the task being spawned. This means that calling spawn while holding a lock does not pose a risk of deadlocking with the spawned task.
+
+                As for these facts, we need that everything inside the spawn block is thread-safe.
+                 */
+                // For every line we spawn async tasks that will perform the protocol readed from stdin. We need to lock shared resources for every tasks in order to avoid concurrency
+                let original = input.clone();
+                tasks.push(tokio::spawn(async move {
+                    let mut node_lock = node_clone.lock().await;
+                    let result = node_lock.step(Event::Message(input), &mut stdout_clone).await;
+                    // Maelstrom clients retry on a `crash` reply rather than hanging on a
+                    // request `step` silently dropped.
+                    if let Err(err) = &result {
+                        let error_reply = original.into_error_reply(ErrorCode::Crash, err.to_string());
+                        let _ = error_reply.send(&mut stdout_clone).await;
+                    }
+                    result
+                }));
+            }
+
+            injected = inject_rx.recv(), if inject_open => {
+                let Some(event) = injected else {
+                    inject_open = false;
+                    continue;
+                };
+
+                let node_clone = Arc::clone(&node);
+                let mut stdout_clone = Arc::clone(&stdout);
+                tasks.push(tokio::spawn(async move {
+                    let mut node_lock = node_clone.lock().await;
+                    node_lock.step(event, &mut stdout_clone).await
+                }));
+            }
+        }
     }
 
     // Wait for every task to finish
     for task in tasks {
         let _ = task.await?;
     }
+    let _ = stdin_task.await?;
 
     Ok(())
 }
@@ -235,3 +271,225 @@ where
 
 // command to run maelstrom server to interactively see transfer between messages, times, traces, etc.
 // ./maelstrom serve
+
+// --- RPC request/reply correlation ---
+// `Message::send` is fire-and-forget; anything that needs to await a reply (the Kv client,
+// inter-node RPC for leader-election-style challenges) goes through RpcSender instead.
+#[async_trait]
+pub trait RpcSender {
+    // Sends `payload` (plus any extra wire metadata in `meta`, e.g. the Kv client's
+    // `key`/`value`) to `target` and resolves with the body of the matching `*_ok` (or
+    // `error`) reply.
+    async fn rpc<Payload, Meta>(
+        &self,
+        target: &ServiceTarget,
+        payload: Payload,
+        meta: Meta,
+    ) -> anyhow::Result<Value>
+    where
+        Payload: Serialize + Send + Sync + 'static,
+        Meta: Serialize + Send + Sync + 'static;
+}
+
+// msg_id -> where to deliver that message's reply once it arrives on stdin.
+type PendingReplies = Arc<Mutex<HashMap<usize, oneshot::Sender<Value>>>>;
+
+// How long an `rpc` call will wait for a reply before giving up, so a partitioned destination
+// can't hang a node forever.
+const RPC_TIMEOUT: Duration = Duration::from_secs(10);
+
+// `main_loop`'s concrete RpcSender: allocates msg_ids, registers a oneshot per outstanding
+// request in `pending`, and relies on the stdin dispatch loop to resolve it when a reply with
+// a matching `in_reply_to` comes back in.
+#[derive(Clone)]
+pub struct RpcHandle {
+    src: String,
+    next_msg_id: Arc<Mutex<usize>>,
+    pending: PendingReplies,
+    output: Arc<Mutex<Stdout>>,
+}
+
+#[async_trait]
+impl RpcSender for RpcHandle {
+    async fn rpc<Payload, Meta>(
+        &self,
+        target: &ServiceTarget,
+        payload: Payload,
+        meta: Meta,
+    ) -> anyhow::Result<Value>
+    where
+        Payload: Serialize + Send + Sync + 'static,
+        Meta: Serialize + Send + Sync + 'static,
+    {
+        let msg_id = {
+            let mut next_msg_id = self.next_msg_id.lock().await;
+            let id = *next_msg_id;
+            *next_msg_id += 1;
+            id
+        };
+
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().await.insert(msg_id, tx);
+
+        // Reuse the same Message/Body envelope every other send goes through: the replying
+        // service (a KV node, or another workload node) speaks a shape our own Payload enum
+        // doesn't know about, so it travels as its own typed payload/meta instead.
+        let request = Message::addressed_to(
+            self.src.clone(),
+            target,
+            Body {
+                id: Some(msg_id),
+                in_reply_to: None,
+                payload,
+                meta,
+            },
+        );
+        request.send(&mut self.output.clone()).await?;
+
+        match timeout(RPC_TIMEOUT, rx).await {
+            Ok(Ok(reply)) => Ok(reply),
+            Ok(Err(_)) => anyhow::bail!("rpc to {} dropped before a reply arrived", target.dst()),
+            Err(_) => {
+                self.pending.lock().await.remove(&msg_id);
+                anyhow::bail!("rpc to {} timed out waiting for in_reply_to={}", target.dst(), msg_id)
+            }
+        }
+    }
+}
+
+// Error returned by Kv::cas. `code` is `Some(ErrorCode::PreconditionFailed)` when the stored
+// value didn't match `from` (Maelstrom error code 22) -- the case callers typically retry on --
+// and `None` for every other failure: any other error reply from the service, a key/value that
+// could not be encoded, or the RPC layer itself (timeout, dropped connection).
+#[derive(Debug, Clone)]
+pub struct CasError {
+    pub code: Option<ErrorCode>,
+    pub text: String,
+}
+
+impl CasError {
+    pub fn is_precondition_failed(&self) -> bool {
+        self.code == Some(ErrorCode::PreconditionFailed)
+    }
+}
+
+impl std::fmt::Display for CasError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "cas failed: {}", self.text)
+    }
+}
+
+impl std::error::Error for CasError {}
+
+// Tag-only payload for a KV request -- `type` is all it carries. The operation's actual fields
+// travel in `KvMeta` below, flattened into the same envelope, so this stays a plain three-variant
+// enum instead of growing a `{key, value, from, to, create_if_not_exists}` field per variant.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum KvOp {
+    Read,
+    Write,
+    Cas,
+}
+
+// Wire metadata for a KV request. Only the fields the operation actually uses are set; the rest
+// are skipped entirely (via `skip_serializing_if`) instead of serializing as `null`.
+#[derive(Debug, Clone, Default, Serialize)]
+struct KvMeta {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    from: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    to: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    create_if_not_exists: Option<bool>,
+}
+
+// Client for one of Maelstrom's built-in key-value services, e.g. `ServiceTarget::SeqKv`.
+pub struct Kv<'a, R: RpcSender> {
+    rpc: &'a R,
+    service: ServiceTarget,
+}
+
+impl<'a, R: RpcSender> Kv<'a, R> {
+    pub fn new(rpc: &'a R, service: ServiceTarget) -> Self {
+        Self { rpc, service }
+    }
+
+    // Reads `key`, returning `None` if the service reports key-does-not-exist (code 20).
+    pub async fn read<K: Serialize, T: DeserializeOwned>(&self, key: K) -> anyhow::Result<Option<T>> {
+        let meta = KvMeta {
+            key: Some(serde_json::to_value(key).context("kv read: encode key")?),
+            ..Default::default()
+        };
+        let reply = self.rpc.rpc(&self.service, KvOp::Read, meta).await.context("kv read")?;
+
+        if reply["type"] == "error" {
+            if reply["code"] == 20 {
+                return Ok(None);
+            }
+            anyhow::bail!("kv read failed: {}", reply["text"]);
+        }
+
+        let value: T = serde_json::from_value(reply["value"].clone()).context("kv read: decode value")?;
+        Ok(Some(value))
+    }
+
+    pub async fn write<K: Serialize, V: Serialize>(&self, key: K, value: V) -> anyhow::Result<()> {
+        let meta = KvMeta {
+            key: Some(serde_json::to_value(key).context("kv write: encode key")?),
+            value: Some(serde_json::to_value(value).context("kv write: encode value")?),
+            ..Default::default()
+        };
+        let reply = self.rpc.rpc(&self.service, KvOp::Write, meta).await.context("kv write")?;
+
+        if reply["type"] == "error" {
+            anyhow::bail!("kv write failed: {}", reply["text"]);
+        }
+        Ok(())
+    }
+
+    // Compare-and-swap: succeeds only if the stored value equals `from`, in which case it is
+    // replaced with `to`. `create_if_not_exists` lets the first writer create the key via cas.
+    pub async fn cas<K: Serialize, V: Serialize>(
+        &self,
+        key: K,
+        from: V,
+        to: V,
+        create_if_not_exists: bool,
+    ) -> Result<(), CasError> {
+        let encode = |label: &str, v: serde_json::Result<Value>| {
+            v.map_err(|e| CasError { code: None, text: format!("kv cas: encode {label}: {e}") })
+        };
+        let meta = KvMeta {
+            key: Some(encode("key", serde_json::to_value(key))?),
+            from: Some(encode("from", serde_json::to_value(from))?),
+            to: Some(encode("to", serde_json::to_value(to))?),
+            create_if_not_exists: Some(create_if_not_exists),
+            ..Default::default()
+        };
+        let reply = self
+            .rpc
+            .rpc(&self.service, KvOp::Cas, meta)
+            .await
+            .map_err(|e| CasError { code: None, text: e.to_string() })?;
+
+        if reply["type"] == "error" {
+            if reply["code"] == 22 {
+                return Err(CasError {
+                    code: Some(ErrorCode::PreconditionFailed),
+                    text: "value did not match `from`".to_string(),
+                });
+            }
+            return Err(CasError {
+                code: None,
+                text: reply["text"].as_str().unwrap_or("unknown cas error").to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+// ./maelstrom serve
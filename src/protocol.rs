@@ -0,0 +1,187 @@
+// Wire envelope shared by every node: the `{src, dest, body}` shape Maelstrom speaks, plus the
+// init handshake and structured error replies. Pulled out of lib.rs so it's one place instead of
+// every binary re-deriving its own copy of the same tag="type" boilerplate.
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+use tokio::{
+    io::{AsyncWriteExt, Stdout},
+    sync::Mutex,
+};
+
+// Struct Message
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Message<Payload, Meta = NoMeta> {
+    pub src: String,
+    #[serde(rename = "dest")]
+    pub dst: String,
+    pub body: Body<Payload, Meta>,
+}
+
+// Implementation of the message for a generic type of payload (and optional wire metadata)
+impl<Payload, Meta> Message<Payload, Meta> {
+    pub fn into_reply(self, id: Option<&mut usize>) -> Self {
+        Self {
+            src: self.dst,
+            dst: self.src,
+            body: Body {
+                id: id.map(|id| {
+                    let mid = *id;
+                    *id += 1;
+                    mid
+                }),
+                in_reply_to: self.body.id,
+                payload: self.body.payload,
+                meta: self.body.meta,
+            },
+        }
+    }
+
+    // Builds an envelope addressed at a `ServiceTarget` (a node, or a built-in KV service)
+    // instead of callers hand-building the `dst` string themselves.
+    pub fn addressed_to(src: impl Into<String>, target: &ServiceTarget, body: Body<Payload, Meta>) -> Self {
+        Self {
+            src: src.into(),
+            dst: target.dst().to_string(),
+            body,
+        }
+    }
+
+    // Send method to reply for different messages
+    pub async fn send<'a>(&self, output: &'a mut Arc<Mutex<Stdout>>) -> anyhow::Result<()>
+    where
+        Payload: Serialize,
+        Meta: Serialize,
+    {
+        let output_clone = Arc::clone(output);
+        let mut output_lock = output_clone.lock().await;
+
+        output_lock
+            .write_all(&serde_json::to_vec(self).expect("Cannot convert to bytes"))
+            .await?;
+        output_lock.write_all(b"\n").await?;
+        Ok(())
+    }
+
+    // Mirrors `into_reply`, but for the cases `step` can't answer with its own Payload: an
+    // unsupported request, a crash, a failed CAS precondition, etc. Maelstrom clients know to
+    // retry (or not) based on `code`.
+    pub fn into_error_reply(self, code: ErrorCode, text: impl Into<String>) -> Message<ErrorPayload> {
+        Message {
+            src: self.dst,
+            dst: self.src,
+            body: Body {
+                id: None,
+                in_reply_to: self.body.id,
+                payload: ErrorPayload::Error {
+                    code,
+                    text: text.into(),
+                },
+                meta: NoMeta::default(),
+            },
+        }
+    }
+}
+
+// Standard Maelstrom error codes (https://github.com/jepsen-io/maelstrom/blob/main/doc/protocol.md#errors).
+// Codes below 1000 are reserved by Maelstrom itself; workloads are free to use anything >= 1000
+// for their own errors, which is why this only models the common ones rather than every value.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize_repr, Deserialize_repr)]
+pub enum ErrorCode {
+    Timeout = 0,
+    NotSupported = 10,
+    TemporarilyUnavailable = 11,
+    MalformedRequest = 12,
+    Crash = 13,
+    Abort = 14,
+    KeyDoesNotExist = 20,
+    KeyAlreadyExists = 21,
+    PreconditionFailed = 22,
+    TxnConflict = 30,
+}
+
+impl ErrorCode {
+    // Definite errors mean the operation definitely did not happen, so a client can safely
+    // retry (or choose not to); indefinite errors mean it may or may not have taken effect.
+    pub fn is_definite(self) -> bool {
+        matches!(
+            self,
+            ErrorCode::NotSupported
+                | ErrorCode::TemporarilyUnavailable
+                | ErrorCode::MalformedRequest
+                | ErrorCode::Abort
+                | ErrorCode::KeyDoesNotExist
+                | ErrorCode::KeyAlreadyExists
+                | ErrorCode::PreconditionFailed
+                | ErrorCode::TxnConflict
+        )
+    }
+
+    pub fn is_indefinite(self) -> bool {
+        !self.is_definite()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ErrorPayload {
+    Error { code: ErrorCode, text: String },
+}
+
+// Body struct. `Meta` flattens in alongside `Payload` for envelopes that need extra wire fields
+// (the KV client's `key`/`value`/`from`/`to`/`create_if_not_exists`) without widening a node's
+// own `Payload` enum with variants it otherwise has no use for.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Body<Payload, Meta = NoMeta> {
+    #[serde(rename = "msg_id")]
+    pub id: Option<usize>,
+    pub in_reply_to: Option<usize>,
+    #[serde(flatten)]
+    pub payload: Payload,
+    #[serde(flatten)]
+    pub meta: Meta,
+}
+
+// Default `Meta`: no extra fields, so every `Body<Payload>`/`Message<Payload>` used by an
+// ordinary node (one with no KV-style metadata to flatten in) is unaffected.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct NoMeta {}
+
+// InitPayload struct
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum InitPayload {
+    Init(Init),
+    InitOk,
+}
+
+// Init struct
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Init {
+    pub node_id: String,
+    pub node_ids: Vec<String>,
+}
+
+// Where a `send`/`rpc` call is headed: one of Maelstrom's built-in KV services, or another node
+// in the cluster. Resolves to the `dest` string on the wire.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServiceTarget {
+    SeqKv,
+    LinKv,
+    LwwKv,
+    Node(String),
+}
+
+impl ServiceTarget {
+    pub fn dst(&self) -> &str {
+        match self {
+            ServiceTarget::SeqKv => "seq-kv",
+            ServiceTarget::LinKv => "lin-kv",
+            ServiceTarget::LwwKv => "lww-kv",
+            ServiceTarget::Node(id) => id,
+        }
+    }
+}